@@ -1,11 +1,18 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod provider;
+mod scheduler;
 mod simple_database;
 
-use std::fs::{create_dir_all, File};
+use scheduler::{ProviderCredentials, SchedulerState};
+use std::sync::atomic::Ordering;
+
+use std::collections::HashSet;
+use std::fs::{create_dir_all, File, OpenOptions};
 use std::io::{Read, Write};
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use tauri::Manager;
 use tauri::Emitter;
@@ -28,6 +35,9 @@ pub struct BatchTaskConfig {
     pub quality: String,
     pub generate_count: Option<i32>,
     pub api_timeout_ms: Option<i32>,
+    // 合批：将参数一致的连续待处理项合并为一次多图生成请求
+    #[serde(default)]
+    pub auto_batch: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -89,6 +99,111 @@ pub struct BatchTask {
     pub error: Option<String>,
 }
 
+// 任务状态词表。以强类型建模，过滤条件在进入 SQL 前必须先解析校验。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TaskStatus {
+    Enqueued,
+    Processing,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl TaskStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskStatus::Enqueued => "enqueued",
+            TaskStatus::Processing => "processing",
+            TaskStatus::Succeeded => "succeeded",
+            TaskStatus::Failed => "failed",
+            TaskStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl std::str::FromStr for TaskStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "enqueued" => Ok(TaskStatus::Enqueued),
+            "processing" => Ok(TaskStatus::Processing),
+            "succeeded" => Ok(TaskStatus::Succeeded),
+            "failed" => Ok(TaskStatus::Failed),
+            "cancelled" => Ok(TaskStatus::Cancelled),
+            other => Err(format!("未知的任务状态: {}", other)),
+        }
+    }
+}
+
+// 任务类型词表：图像生成与视频生成。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TaskType {
+    Image,
+    Video,
+}
+
+impl TaskType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskType::Image => "image",
+            TaskType::Video => "video",
+        }
+    }
+}
+
+impl std::str::FromStr for TaskType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "image" => Ok(TaskType::Image),
+            "video" => Ok(TaskType::Video),
+            other => Err(format!("未知的任务类型: {}", other)),
+        }
+    }
+}
+
+// query_batch_tasks 的过滤条件，来自前端的原始字符串在这里被校验为枚举
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskQueryFilter {
+    pub status: Option<String>,
+    pub r#type: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+// 轻量任务摘要，不反序列化 config/items/results 等重型 JSON 列
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchTaskSummary {
+    pub id: String,
+    pub name: String,
+    pub r#type: String,
+    pub status: String,
+    pub progress: i32,
+    pub total_items: i32,
+    pub completed_items: i32,
+    pub failed_items: i32,
+    pub created_at: String,
+    pub started_at: Option<String>,
+    pub completed_at: Option<String>,
+}
+
+// 全文检索命中：任务/项目定位信息与匹配到的提示词片段
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub task_id: String,
+    pub item_id: String,
+    pub name: String,
+    pub prompt: String,
+    pub snippet: String,
+}
+
 #[tauri::command]
 fn read_local_file(path: String) -> Result<String, String> {
     use std::fs::File;
@@ -129,8 +244,39 @@ fn get_download_dir(app_handle: tauri::AppHandle) -> Result<String, String> {
     Ok(dir.to_string_lossy().to_string())
 }
 
+// 已请求取消下载的 URL 集合，下载循环每读取一个分块都会检查一次
+#[derive(Default)]
+struct DownloadState {
+    canceled: Arc<Mutex<HashSet<String>>>,
+}
+
+impl DownloadState {
+    fn is_canceled(&self, url: &str) -> bool {
+        self.canceled.lock().unwrap().contains(url)
+    }
+
+    fn clear(&self, url: &str) {
+        self.canceled.lock().unwrap().remove(url);
+    }
+
+    fn request_cancel(&self, url: &str) {
+        self.canceled.lock().unwrap().insert(url.to_string());
+    }
+}
+
+// 从 `Content-Range: bytes a-b/total` 解析出资源总大小
+fn parse_content_range_total(value: &str) -> Option<u64> {
+    value.split('/').nth(1).and_then(|s| s.trim().parse::<u64>().ok())
+}
+
 #[tauri::command]
-fn download_file(url: String, filename: String, dir: Option<String>, app_handle: tauri::AppHandle) -> Result<String, String> {
+fn download_file(
+    url: String,
+    filename: String,
+    dir: Option<String>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, DownloadState>,
+) -> Result<String, String> {
     let save_dir = match dir {
         Some(d) if !d.is_empty() => PathBuf::from(d),
         _ => app_handle
@@ -148,6 +294,15 @@ fn download_file(url: String, filename: String, dir: Option<String>, app_handle:
         }
     }
 
+    // 断点续传：已下载的数据落在同名 `.part` 文件中，完成后再原子改名
+    let part_path = save_path.with_extension(match save_path.extension() {
+        Some(ext) => format!("{}.part", ext.to_string_lossy()),
+        None => "part".to_string(),
+    });
+
+    // 进入下载前清掉可能残留的取消标记
+    state.clear(&url);
+
     // HTTP 客户端，设置 UA/Referer 与超时
     let client = reqwest::blocking::Client::builder()
         .timeout(Duration::from_secs(60))
@@ -155,43 +310,72 @@ fn download_file(url: String, filename: String, dir: Option<String>, app_handle:
         .build()
         .map_err(|e| format!("构建HTTP客户端失败: {}", e))?;
 
-    // 重试 3 次
+    // 重试 3 次，每次从 `.part` 的现有长度处继续，而不是从 0 重来
     let mut last_err: Option<String> = None;
     for attempt in 0..3 {
-        let req = client
-            .get(&url)
-            .header("Referer", "http://localhost")
-            .build()
-            .map_err(|e| format!("构建请求失败: {}", e))?;
+        let existing_len = std::fs::metadata(&part_path).map(|m| m.len()).unwrap_or(0);
+
+        let mut builder = client.get(&url).header("Referer", "http://localhost");
+        if existing_len > 0 {
+            builder = builder.header(reqwest::header::RANGE, format!("bytes={}-", existing_len));
+        }
+        let req = match builder.build() {
+            Ok(r) => r,
+            Err(e) => return Err(format!("构建请求失败: {}", e)),
+        };
 
         match client.execute(req) {
             Ok(mut resp) => {
-                if !resp.status().is_success() {
-                    last_err = Some(format!("HTTP {}", resp.status()));
+                let status = resp.status();
+                if !status.is_success() {
+                    last_err = Some(format!("HTTP {}", status));
+                    std::thread::sleep(Duration::from_millis(300 * (attempt + 1) as u64));
                     continue;
                 }
 
-                let total = resp
+                // 206 表示服务器接受了 Range，追加写入；否则（200）忽略了 Range，截断重下
+                let resume = status == reqwest::StatusCode::PARTIAL_CONTENT && existing_len > 0;
+                let content_len = resp
                     .headers()
                     .get(reqwest::header::CONTENT_LENGTH)
                     .and_then(|v| v.to_str().ok())
                     .and_then(|s| s.parse::<u64>().ok())
                     .unwrap_or(0);
+                let total = resp
+                    .headers()
+                    .get(reqwest::header::CONTENT_RANGE)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(parse_content_range_total)
+                    .unwrap_or_else(|| if resume { existing_len + content_len } else { content_len });
 
-                let mut file = match File::create(&save_path) {
-                    Ok(f) => f,
-                    Err(e) => return Err(format!("创建文件失败: {}", e)),
+                let mut file = if resume {
+                    match OpenOptions::new().append(true).open(&part_path) {
+                        Ok(f) => f,
+                        Err(e) => return Err(format!("打开续传文件失败: {}", e)),
+                    }
+                } else {
+                    match File::create(&part_path) {
+                        Ok(f) => f,
+                        Err(e) => return Err(format!("创建文件失败: {}", e)),
+                    }
                 };
 
-                let mut downloaded: u64 = 0;
+                let mut downloaded: u64 = if resume { existing_len } else { 0 };
                 let mut buffer = [0u8; 64 * 1024];
                 let start = Instant::now();
+                // 本次尝试是否因读取出错而中断，每次尝试独立判断
+                let mut stream_err: Option<String> = None;
                 loop {
+                    // 每个分块检查一次取消标记，保留 `.part` 以便后续续传
+                    if state.is_canceled(&url) {
+                        state.clear(&url);
+                        return Err("下载已取消".to_string());
+                    }
                     let n = match resp.read(&mut buffer) {
                         Ok(0) => break,
                         Ok(n) => n,
                         Err(e) => {
-                            let _ = last_err.insert(format!("读取流失败: {}", e));
+                            stream_err = Some(format!("读取流失败: {}", e));
                             break;
                         }
                     };
@@ -202,7 +386,8 @@ fn download_file(url: String, filename: String, dir: Option<String>, app_handle:
 
                     // 上报进度
                     let elapsed = start.elapsed().as_secs_f64();
-                    let speed = if elapsed > 0.0 { (downloaded as f64 / elapsed) as u64 } else { 0 };
+                    let session = downloaded.saturating_sub(if resume { existing_len } else { 0 });
+                    let speed = if elapsed > 0.0 { (session as f64 / elapsed) as u64 } else { 0 };
                     let _ = app_handle.emit(
                         "download:progress",
                         serde_json::json!({
@@ -215,7 +400,17 @@ fn download_file(url: String, filename: String, dir: Option<String>, app_handle:
                     );
                 }
 
-                // 成功
+                // 本次尝试读取中途出错则重试，保留 `.part` 继续累积；
+                // 读到 EOF（无错误）即视为完成，不依赖是否已知总大小
+                if let Some(e) = stream_err {
+                    last_err = Some(e);
+                    continue;
+                }
+
+                // 完成：原子改名 `.part` -> 最终文件
+                drop(file);
+                std::fs::rename(&part_path, &save_path)
+                    .map_err(|e| format!("重命名文件失败: {}", e))?;
                 return Ok(save_path.to_string_lossy().to_string());
             }
             Err(e) => {
@@ -229,6 +424,13 @@ fn download_file(url: String, filename: String, dir: Option<String>, app_handle:
     Err(last_err.unwrap_or_else(|| "下载失败".to_string()))
 }
 
+// 请求取消某个 URL 的下载；已下载的 `.part` 文件保留，可稍后续传
+#[tauri::command]
+fn cancel_download(url: String, state: tauri::State<'_, DownloadState>) -> Result<(), String> {
+    state.request_cancel(&url);
+    Ok(())
+}
+
 #[tauri::command]
 fn get_machine_id() -> Result<String, String> {
     let mut sys = System::new_all();
@@ -313,19 +515,312 @@ async fn cleanup_old_tasks(app_handle: tauri::AppHandle, max_tasks_to_keep: Opti
         .map_err(|e| format!("清理旧任务失败: {}", e))
 }
 
+// 带状态/类型过滤与分页的任务查询，返回轻量摘要
+#[tauri::command]
+async fn query_batch_tasks(
+    app_handle: tauri::AppHandle,
+    filter: Option<TaskQueryFilter>,
+) -> Result<Vec<BatchTaskSummary>, String> {
+    simple_database::SimpleDatabase::init_db(&app_handle).await?;
+
+    let filter = filter.unwrap_or_default();
+    // 先把原始字符串校验为枚举，避免把任意字符串拼进 SQL
+    let status = match &filter.status {
+        Some(s) => Some(s.parse::<TaskStatus>()?),
+        None => None,
+    };
+    let r#type = match &filter.r#type {
+        Some(t) => Some(t.parse::<TaskType>()?),
+        None => None,
+    };
+
+    simple_database::SimpleDatabase::query_batch_tasks(
+        &app_handle,
+        status,
+        r#type,
+        filter.limit,
+        filter.offset,
+    )
+    .await
+    .map_err(|e| format!("查询任务失败: {}", e))
+}
+
+// 获取单个任务的完整对象（含 config/items/results）
+#[tauri::command]
+async fn get_batch_task(
+    app_handle: tauri::AppHandle,
+    task_id: String,
+) -> Result<Option<BatchTask>, String> {
+    simple_database::SimpleDatabase::init_db(&app_handle).await?;
+
+    simple_database::SimpleDatabase::get_batch_task(&app_handle, &task_id)
+        .await
+        .map_err(|e| format!("获取任务失败: {}", e))
+}
+
+// 全文检索任务提示词与名称
+#[tauri::command]
+async fn search_tasks(
+    app_handle: tauri::AppHandle,
+    query: String,
+    limit: Option<i64>,
+) -> Result<Vec<SearchHit>, String> {
+    simple_database::SimpleDatabase::init_db(&app_handle).await?;
+
+    simple_database::SimpleDatabase::search_tasks(&app_handle, &query, limit)
+        .await
+        .map_err(|e| format!("检索任务失败: {}", e))
+}
+
+// 可移植的任务导出包：JSON 清单 + 内嵌（base64）图片文件，便于在机器间迁移
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskDump {
+    pub schema_version: i64,
+    pub exported_at: String,
+    pub tasks: Vec<BatchTask>,
+    // 文件名 -> base64 内容，对应各 TaskResult 引用的 local_path 图片
+    pub images: std::collections::HashMap<String, String>,
+}
+
+// 导入模式：merge 跳过已存在的任务，replace 覆盖同 id 的任务
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    Merge,
+    Replace,
+}
+
+impl std::str::FromStr for ImportMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "merge" => Ok(ImportMode::Merge),
+            "replace" => Ok(ImportMode::Replace),
+            other => Err(format!("未知的导入模式: {}", other)),
+        }
+    }
+}
+
+// 为转储内的图片生成无碰撞的条目名：完整路径的哈希前缀 + 原文件名，
+// 确保不同目录下的同名文件不会互相覆盖，且同一路径在导入时能稳定命中
+fn bundled_image_name(local_path: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(local_path.as_bytes());
+    let digest = format!("{:x}", hasher.finalize());
+    let base = PathBuf::from(local_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "image".to_string());
+    format!("{}_{}", &digest[..8], base)
+}
+
+// 把整个任务库导出为一个可移植的转储文件，大历史会分步上报进度
+#[tauri::command]
+async fn export_tasks(app_handle: tauri::AppHandle, path: String) -> Result<(), String> {
+    use base64::{engine::general_purpose, Engine as _};
+
+    simple_database::SimpleDatabase::init_db(&app_handle).await?;
+    let tasks = simple_database::SimpleDatabase::get_all_batch_tasks(&app_handle)
+        .await
+        .map_err(|e| format!("获取任务失败: {}", e))?;
+
+    let total = tasks.len();
+    let mut images: std::collections::HashMap<String, String> = std::collections::HashMap::new();
+    for (idx, task) in tasks.iter().enumerate() {
+        for result in &task.results {
+            if let Some(local_path) = &result.local_path {
+                let name = bundled_image_name(local_path);
+                if !images.contains_key(&name) {
+                    if let Ok(bytes) = std::fs::read(local_path) {
+                        images.insert(name, general_purpose::STANDARD.encode(&bytes));
+                    }
+                }
+            }
+        }
+        let _ = app_handle.emit(
+            "export:progress",
+            serde_json::json!({ "current": idx + 1, "total": total }),
+        );
+    }
+
+    let dump = TaskDump {
+        schema_version: simple_database::SimpleDatabase::current_schema_version(),
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        tasks,
+        images,
+    };
+
+    let json = serde_json::to_string_pretty(&dump)
+        .map_err(|e| format!("序列化导出内容失败: {}", e))?;
+    std::fs::write(&path, json).map_err(|e| format!("写入导出文件失败: {}", e))?;
+
+    Ok(())
+}
+
+// 从转储文件恢复任务：按 id 去重，重写 local_path 到本机下载目录
+#[tauri::command]
+async fn import_tasks(app_handle: tauri::AppHandle, path: String, mode: String) -> Result<i64, String> {
+    use base64::{engine::general_purpose, Engine as _};
+
+    let mode: ImportMode = mode.parse()?;
+    simple_database::SimpleDatabase::init_db(&app_handle).await?;
+
+    let raw = std::fs::read_to_string(&path).map_err(|e| format!("读取导入文件失败: {}", e))?;
+    let dump: TaskDump = serde_json::from_str(&raw).map_err(|e| format!("解析导入文件失败: {}", e))?;
+
+    // 校验 schema 版本：不能导入比当前代码更新的转储
+    let current = simple_database::SimpleDatabase::current_schema_version();
+    if dump.schema_version > current {
+        return Err(format!(
+            "导出文件的 schema 版本({})高于当前版本({}),请先升级应用",
+            dump.schema_version, current
+        ));
+    }
+
+    let download_dir = app_handle
+        .path()
+        .download_dir()
+        .map_err(|e| format!("无法获取下载目录: {}", e))?;
+    create_dir_all(&download_dir).map_err(|e| format!("创建下载目录失败: {}", e))?;
+
+    let total = dump.tasks.len();
+    let mut imported = 0i64;
+    for (idx, mut task) in dump.tasks.into_iter().enumerate() {
+        let exists = simple_database::SimpleDatabase::get_batch_task(&app_handle, &task.id)
+            .await
+            .map_err(|e| format!("查询任务失败: {}", e))?
+            .is_some();
+        if exists && mode == ImportMode::Merge {
+            let _ = app_handle.emit(
+                "import:progress",
+                serde_json::json!({ "current": idx + 1, "total": total }),
+            );
+            continue;
+        }
+
+        // 把内嵌图片落盘到本机下载目录，并重写 local_path
+        for result in &mut task.results {
+            if let Some(local_path) = result.local_path.clone() {
+                let name = bundled_image_name(&local_path);
+                if let Some(b64) = dump.images.get(&name) {
+                    if let Ok(bytes) = general_purpose::STANDARD.decode(b64) {
+                        let dest = download_dir.join(&name);
+                        let _ = std::fs::write(&dest, bytes);
+                        result.local_path = Some(dest.to_string_lossy().to_string());
+                    }
+                }
+            }
+        }
+
+        simple_database::SimpleDatabase::save_batch_task(&app_handle, &task)
+            .await
+            .map_err(|e| format!("保存任务失败: {}", e))?;
+        imported += 1;
+
+        let _ = app_handle.emit(
+            "import:progress",
+            serde_json::json!({ "current": idx + 1, "total": total }),
+        );
+    }
+
+    Ok(imported)
+}
+
+// 在 Rust 后端启动批量任务：驱动每个 TaskItem 走完生成流程
+#[tauri::command]
+async fn start_batch_task(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, SchedulerState>,
+    task_id: String,
+    api_base: String,
+    api_key: String,
+) -> Result<(), String> {
+    simple_database::SimpleDatabase::init_db(&app_handle).await?;
+
+    let task = simple_database::SimpleDatabase::get_batch_task(&app_handle, &task_id)
+        .await
+        .map_err(|e| format!("获取任务失败: {}", e))?
+        .ok_or_else(|| format!("任务不存在: {}", task_id))?;
+
+    let control = state.register(&task_id);
+    let running = state.handle();
+    let creds = ProviderCredentials { api_base, api_key };
+
+    // 在后台驱动任务，即使前端重载也能继续推进；完成后把自己从运行表移除
+    let handle = app_handle.clone();
+    tokio::spawn(async move {
+        if let Err(e) = scheduler::drive_task(handle, control, creds, task).await {
+            eprintln!("批量任务执行失败: {}", e);
+        }
+        scheduler::evict(&running, &task_id);
+    });
+
+    Ok(())
+}
+
+// 暂停正在运行的批量任务（已在跑的项目完成后不再调度新项目）
+#[tauri::command]
+fn pause_batch_task(state: tauri::State<'_, SchedulerState>, task_id: String) -> Result<(), String> {
+    match state.get(&task_id) {
+        Some(control) => {
+            control.paused.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+        None => Err(format!("任务未在运行: {}", task_id)),
+    }
+}
+
+// 恢复此前暂停的批量任务
+#[tauri::command]
+fn resume_batch_task(state: tauri::State<'_, SchedulerState>, task_id: String) -> Result<(), String> {
+    match state.get(&task_id) {
+        Some(control) => {
+            control.paused.store(false, Ordering::SeqCst);
+            Ok(())
+        }
+        None => Err(format!("任务未在运行: {}", task_id)),
+    }
+}
+
+// 取消批量任务，停止调度剩余项目
+#[tauri::command]
+fn cancel_batch_task(state: tauri::State<'_, SchedulerState>, task_id: String) -> Result<(), String> {
+    match state.get(&task_id) {
+        Some(control) => {
+            control.canceled.store(true, Ordering::SeqCst);
+            control.paused.store(false, Ordering::SeqCst);
+            Ok(())
+        }
+        None => Err(format!("任务未在运行: {}", task_id)),
+    }
+}
+
 fn main() {
     tauri::Builder::default()
+        .manage(SchedulerState::default())
+        .manage(DownloadState::default())
         .invoke_handler(tauri::generate_handler![
             read_local_file,
             get_download_dir,
             download_file,
+            cancel_download,
             get_machine_id,
             get_batch_tasks,
             save_batch_task,
             delete_batch_task,
             clear_batch_tasks,
             get_task_count,
-            cleanup_old_tasks
+            cleanup_old_tasks,
+            query_batch_tasks,
+            get_batch_task,
+            search_tasks,
+            export_tasks,
+            import_tasks,
+            start_batch_task,
+            pause_batch_task,
+            resume_batch_task,
+            cancel_batch_task
         ])
         .setup(|_app| {
             #[cfg(debug_assertions)]
@@ -0,0 +1,102 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+// 向上游（OpenAI 兼容）图像生成接口发起请求的参数
+#[derive(Debug, Clone)]
+pub struct GenRequest {
+    pub api_base: String,
+    pub api_key: String,
+    pub model: String,
+    pub model_type: String,
+    pub prompt: String,
+    pub size: String,
+    pub aspect_ratio: String,
+    pub quality: String,
+    // 一次请求希望返回的图片数量，对应 auto_batch 合批后的项目数
+    pub generate_count: i32,
+    pub source_image: Option<String>,
+    pub mask: Option<String>,
+    pub timeout_ms: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImageData {
+    url: Option<String>,
+    #[serde(rename = "b64_json")]
+    b64_json: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImageResponse {
+    data: Vec<ImageData>,
+}
+
+// 调用上游接口生成图片，返回图片 URL（或 data: 形式的 base64）列表。
+// 返回的数量理论上等于 req.generate_count，调用方据此把结果拆分回各个任务项。
+pub async fn generate(req: &GenRequest) -> Result<Vec<String>, String> {
+    let timeout = Duration::from_millis(req.timeout_ms.unwrap_or(60_000).max(1) as u64);
+    let client = reqwest::Client::builder()
+        .timeout(timeout)
+        .build()
+        .map_err(|e| format!("构建HTTP客户端失败: {}", e))?;
+
+    let n = req.generate_count.max(1);
+    let mut body = serde_json::json!({
+        "model": req.model,
+        "prompt": req.prompt,
+        "n": n,
+        "size": req.size,
+    });
+    if !req.model_type.is_empty() {
+        body["model_type"] = serde_json::Value::String(req.model_type.clone());
+    }
+    if !req.quality.is_empty() {
+        body["quality"] = serde_json::Value::String(req.quality.clone());
+    }
+    if !req.aspect_ratio.is_empty() {
+        body["aspect_ratio"] = serde_json::Value::String(req.aspect_ratio.clone());
+    }
+    if let Some(img) = &req.source_image {
+        body["image"] = serde_json::Value::String(img.clone());
+    }
+    if let Some(mask) = &req.mask {
+        body["mask"] = serde_json::Value::String(mask.clone());
+    }
+
+    let base = req.api_base.trim_end_matches('/');
+    let url = format!("{}/v1/images/generations", base);
+
+    let resp = client
+        .post(&url)
+        .bearer_auth(&req.api_key)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| format!("请求生成接口失败: {}", e))?;
+
+    let status = resp.status();
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!("生成接口返回 HTTP {}: {}", status, text));
+    }
+
+    let parsed: ImageResponse = resp
+        .json()
+        .await
+        .map_err(|e| format!("解析生成结果失败: {}", e))?;
+
+    let urls: Vec<String> = parsed
+        .data
+        .into_iter()
+        .filter_map(|d| {
+            d.url
+                .or_else(|| d.b64_json.map(|b| format!("data:image/png;base64,{}", b)))
+        })
+        .collect();
+
+    if urls.is_empty() {
+        return Err("生成接口未返回任何图片".to_string());
+    }
+
+    Ok(urls)
+}
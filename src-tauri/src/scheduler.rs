@@ -0,0 +1,491 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::{Mutex, Semaphore};
+
+use crate::provider::{self, GenRequest};
+use crate::simple_database::SimpleDatabase;
+use crate::{BatchTask, TaskItem, TaskResult};
+
+// 每个正在运行的任务的控制句柄：支持暂停与取消
+pub struct TaskControl {
+    pub paused: AtomicBool,
+    pub canceled: AtomicBool,
+}
+
+impl TaskControl {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            paused: AtomicBool::new(false),
+            canceled: AtomicBool::new(false),
+        })
+    }
+}
+
+// 正在运行的任务表，可被克隆到后台任务中以便自行注销
+pub type RunningTasks = Arc<std::sync::Mutex<HashMap<String, Arc<TaskControl>>>>;
+
+// 调度器状态，登记在 Tauri 的托管状态中，记录当前正在运行的任务
+#[derive(Default)]
+pub struct SchedulerState {
+    running: RunningTasks,
+}
+
+impl SchedulerState {
+    pub fn register(&self, task_id: &str) -> Arc<TaskControl> {
+        let control = TaskControl::new();
+        self.running
+            .lock()
+            .unwrap()
+            .insert(task_id.to_string(), control.clone());
+        control
+    }
+
+    pub fn get(&self, task_id: &str) -> Option<Arc<TaskControl>> {
+        self.running.lock().unwrap().get(task_id).cloned()
+    }
+
+    // 取出运行表句柄，供后台任务完成时自行注销
+    pub fn handle(&self) -> RunningTasks {
+        self.running.clone()
+    }
+}
+
+// 从运行表移除已结束的任务
+pub fn evict(running: &RunningTasks, task_id: &str) {
+    running.lock().unwrap().remove(task_id);
+}
+
+// 上报给前端的进度事件负载
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ProgressPayload {
+    task_id: String,
+    status: String,
+    progress: i32,
+    completed_items: i32,
+    failed_items: i32,
+    total_items: i32,
+}
+
+// 生成时所需的上游凭证，随任务一并传入
+#[derive(Clone)]
+pub struct ProviderCredentials {
+    pub api_base: String,
+    pub api_key: String,
+}
+
+fn now_iso() -> String {
+    chrono::Utc::now().to_rfc3339()
+}
+
+// 重新统计进度与完成/失败计数，持久化并向前端推送进度事件
+async fn persist_and_emit(
+    app_handle: &AppHandle,
+    task: &Arc<Mutex<BatchTask>>,
+) -> Result<(), String> {
+    let mut guard = task.lock().await;
+
+    let total = guard.items.len() as i32;
+    let completed = guard
+        .items
+        .iter()
+        .filter(|i| i.status == "succeeded")
+        .count() as i32;
+    let failed = guard
+        .items
+        .iter()
+        .filter(|i| i.status == "failed")
+        .count() as i32;
+
+    guard.total_items = total;
+    guard.completed_items = completed;
+    guard.failed_items = failed;
+    guard.progress = if total > 0 {
+        ((completed + failed) as f64 / total as f64 * 100.0).round() as i32
+    } else {
+        0
+    };
+
+    let snapshot = guard.clone();
+    drop(guard);
+
+    SimpleDatabase::save_batch_task(app_handle, &snapshot).await?;
+
+    let _ = app_handle.emit(
+        "batch:progress",
+        ProgressPayload {
+            task_id: snapshot.id.clone(),
+            status: snapshot.status.clone(),
+            progress: snapshot.progress,
+            completed_items: snapshot.completed_items,
+            failed_items: snapshot.failed_items,
+            total_items: snapshot.total_items,
+        },
+    );
+
+    Ok(())
+}
+
+// 构造单个任务项对应的生成请求
+fn build_request(
+    creds: &ProviderCredentials,
+    task: &BatchTask,
+    item: &TaskItem,
+    generate_count: i32,
+) -> GenRequest {
+    GenRequest {
+        api_base: creds.api_base.clone(),
+        api_key: creds.api_key.clone(),
+        model: task.config.model.clone(),
+        model_type: task.config.model_type.clone(),
+        prompt: item.prompt.clone(),
+        size: task.config.size.clone(),
+        aspect_ratio: task.config.aspect_ratio.clone(),
+        quality: task.config.quality.clone(),
+        generate_count,
+        source_image: item.source_image.clone(),
+        mask: item.mask.clone(),
+        timeout_ms: task.config.api_timeout_ms,
+    }
+}
+
+// 上游接口单次请求的最大出图数（OpenAI 兼容接口上限为 10）
+const MAX_BATCH_IMAGES: usize = 10;
+
+// 扫描待处理项（按 priority 排序），把相邻、提示词相同的 text2img 项累积为一批；
+// 因为请求用的是 `n` 参数——返回同一提示词的 N 张图，所以只有提示词完全一致的项
+// 才能合批；遇到蒙版/图生图项、不同提示词或达到出图上限时，当前批即结束。
+fn autobatch_groups(task: &BatchTask) -> Vec<Vec<String>> {
+    let mut pending: Vec<&TaskItem> = task
+        .items
+        .iter()
+        .filter(|i| i.status != "succeeded")
+        .collect();
+    pending.sort_by(|a, b| b.priority.cmp(&a.priority));
+
+    // 合批上限：受接口出图上限与 config.generate_count（若设置）共同约束
+    let cap = task
+        .config
+        .generate_count
+        .filter(|c| *c > 0)
+        .map(|c| (c as usize).min(MAX_BATCH_IMAGES))
+        .unwrap_or(MAX_BATCH_IMAGES)
+        .max(1);
+
+    let mut groups: Vec<Vec<String>> = Vec::new();
+    let mut run: Vec<String> = Vec::new();
+    let mut run_prompt: Option<&str> = None;
+
+    for item in pending {
+        // 带图生图/蒙版输入的项目不参与合批，单独成批走逐项生成
+        if item.source_image.is_some() || item.mask.is_some() {
+            if !run.is_empty() {
+                groups.push(std::mem::take(&mut run));
+                run_prompt = None;
+            }
+            groups.push(vec![item.id.clone()]);
+            continue;
+        }
+
+        // 只有提示词与当前批一致、且未超过出图上限时才继续累积
+        let fits = run_prompt == Some(item.prompt.as_str()) && run.len() < cap;
+        if !fits {
+            if !run.is_empty() {
+                groups.push(std::mem::take(&mut run));
+            }
+            run_prompt = Some(item.prompt.as_str());
+        }
+        run.push(item.id.clone());
+    }
+    if !run.is_empty() {
+        groups.push(run);
+    }
+    groups
+}
+
+// 合批生成：一次请求生成 generate_count 张图，再把返回的图片按顺序派发回每个项目
+async fn run_batch(
+    app_handle: AppHandle,
+    task: Arc<Mutex<BatchTask>>,
+    control: Arc<TaskControl>,
+    creds: ProviderCredentials,
+    item_ids: Vec<String>,
+) {
+    if control.canceled.load(Ordering::SeqCst) {
+        return;
+    }
+    while control.paused.load(Ordering::SeqCst) && !control.canceled.load(Ordering::SeqCst) {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    if control.canceled.load(Ordering::SeqCst) {
+        return;
+    }
+
+    let (request, retry_attempts, retry_delay) = {
+        let mut guard = task.lock().await;
+        let retry_attempts = guard.config.retry_attempts.max(1);
+        let retry_delay = guard.config.retry_delay.max(0);
+        let first = match guard.items.iter().find(|i| i.id == item_ids[0]) {
+            Some(i) => i.clone(),
+            None => return,
+        };
+        let request = build_request(&creds, &guard, &first, item_ids.len() as i32);
+        for id in &item_ids {
+            if let Some(item) = guard.items.iter_mut().find(|i| &i.id == id) {
+                item.status = "processing".to_string();
+            }
+        }
+        (request, retry_attempts, retry_delay)
+    };
+    let _ = persist_and_emit(&app_handle, &task).await;
+
+    let mut last_err: Option<String> = None;
+    for attempt in 1..=retry_attempts {
+        if control.canceled.load(Ordering::SeqCst) {
+            return;
+        }
+        {
+            let mut guard = task.lock().await;
+            for id in &item_ids {
+                if let Some(item) = guard.items.iter_mut().find(|i| &i.id == id) {
+                    item.attempt_count = attempt;
+                }
+            }
+        }
+
+        match provider::generate(&request).await {
+            Ok(urls) => {
+                let mut guard = task.lock().await;
+                // 严格按下标派发：第 idx 个项目取第 idx 张图；
+                // 没有对应图片的项目标记为失败，而不是伪造成功
+                for (idx, id) in item_ids.iter().enumerate() {
+                    match urls.get(idx).cloned() {
+                        Some(url) => {
+                            guard.results.push(TaskResult {
+                                id: format!("{}-{}", id, guard.results.len()),
+                                task_item_id: id.clone(),
+                                image_url: url,
+                                local_path: None,
+                                downloaded: false,
+                                created_at: now_iso(),
+                                duration_ms: None,
+                            });
+                            if let Some(item) = guard.items.iter_mut().find(|i| &i.id == id) {
+                                item.status = "succeeded".to_string();
+                                item.processed_at = Some(now_iso());
+                                item.error = None;
+                            }
+                        }
+                        None => {
+                            if let Some(item) = guard.items.iter_mut().find(|i| &i.id == id) {
+                                item.status = "failed".to_string();
+                                item.processed_at = Some(now_iso());
+                                item.error = Some("合批生成未返回对应图片".to_string());
+                            }
+                        }
+                    }
+                }
+                drop(guard);
+                let _ = persist_and_emit(&app_handle, &task).await;
+                return;
+            }
+            Err(e) => {
+                last_err = Some(e);
+                if attempt < retry_attempts {
+                    let backoff = retry_delay as u64 * attempt as u64;
+                    tokio::time::sleep(Duration::from_millis(backoff)).await;
+                }
+            }
+        }
+    }
+
+    {
+        let mut guard = task.lock().await;
+        for id in &item_ids {
+            if let Some(item) = guard.items.iter_mut().find(|i| &i.id == id) {
+                item.status = "failed".to_string();
+                item.processed_at = Some(now_iso());
+                item.error = last_err.clone();
+            }
+        }
+    }
+    let _ = persist_and_emit(&app_handle, &task).await;
+}
+
+// 将单个任务项驱动到完成：按 retry_attempts 重试，退避 retry_delay * attempt 毫秒
+async fn run_item(
+    app_handle: AppHandle,
+    task: Arc<Mutex<BatchTask>>,
+    control: Arc<TaskControl>,
+    creds: ProviderCredentials,
+    item_id: String,
+) {
+    // 取消：直接退出，保持当前状态
+    if control.canceled.load(Ordering::SeqCst) {
+        return;
+    }
+
+    // 暂停时自旋等待，直到恢复或取消
+    while control.paused.load(Ordering::SeqCst) && !control.canceled.load(Ordering::SeqCst) {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    if control.canceled.load(Ordering::SeqCst) {
+        return;
+    }
+
+    let (request, retry_attempts, retry_delay) = {
+        let mut guard = task.lock().await;
+        let retry_attempts = guard.config.retry_attempts.max(1);
+        let retry_delay = guard.config.retry_delay.max(0);
+        let request = {
+            let item = match guard.items.iter().find(|i| i.id == item_id) {
+                Some(i) => i.clone(),
+                None => return,
+            };
+            build_request(&creds, &guard, &item, 1)
+        };
+        if let Some(item) = guard.items.iter_mut().find(|i| i.id == item_id) {
+            item.status = "processing".to_string();
+        }
+        (request, retry_attempts, retry_delay)
+    };
+    let _ = persist_and_emit(&app_handle, &task).await;
+
+    let mut last_err: Option<String> = None;
+    for attempt in 1..=retry_attempts {
+        if control.canceled.load(Ordering::SeqCst) {
+            return;
+        }
+        // 记录本次尝试次数
+        {
+            let mut guard = task.lock().await;
+            if let Some(item) = guard.items.iter_mut().find(|i| i.id == item_id) {
+                item.attempt_count = attempt;
+            }
+        }
+
+        match provider::generate(&request).await {
+            Ok(urls) => {
+                let mut guard = task.lock().await;
+                for url in urls {
+                    guard.results.push(TaskResult {
+                        id: format!("{}-{}", item_id, guard.results.len()),
+                        task_item_id: item_id.clone(),
+                        image_url: url,
+                        local_path: None,
+                        downloaded: false,
+                        created_at: now_iso(),
+                        duration_ms: None,
+                    });
+                }
+                if let Some(item) = guard.items.iter_mut().find(|i| i.id == item_id) {
+                    item.status = "succeeded".to_string();
+                    item.processed_at = Some(now_iso());
+                    item.error = None;
+                }
+                drop(guard);
+                let _ = persist_and_emit(&app_handle, &task).await;
+                return;
+            }
+            Err(e) => {
+                last_err = Some(e);
+                if attempt < retry_attempts {
+                    let backoff = retry_delay as u64 * attempt as u64;
+                    tokio::time::sleep(Duration::from_millis(backoff)).await;
+                }
+            }
+        }
+    }
+
+    // 重试耗尽，标记失败
+    {
+        let mut guard = task.lock().await;
+        if let Some(item) = guard.items.iter_mut().find(|i| i.id == item_id) {
+            item.status = "failed".to_string();
+            item.processed_at = Some(now_iso());
+            item.error = last_err.clone();
+        }
+    }
+    let _ = persist_and_emit(&app_handle, &task).await;
+}
+
+// 驱动整个批量任务：按 concurrent_limit 限制并发，一个信号量控制同时运行的调度单元数
+// （单个项目或一个合批请求各占一个许可，开启 auto_batch 时同时在跑的项目数可能多于 concurrent_limit）
+pub async fn drive_task(
+    app_handle: AppHandle,
+    control: Arc<TaskControl>,
+    creds: ProviderCredentials,
+    mut task: BatchTask,
+) -> Result<(), String> {
+    let concurrent = task.config.concurrent_limit.max(1) as usize;
+
+    task.status = "processing".to_string();
+    if task.started_at.is_none() {
+        task.started_at = Some(now_iso());
+    }
+
+    let shared = Arc::new(Mutex::new(task));
+    let semaphore = Arc::new(Semaphore::new(concurrent));
+    persist_and_emit(&app_handle, &shared).await?;
+
+    // 规划调度单元：开启 auto_batch 时先做合批，否则每个待处理项各自成单元
+    let groups: Vec<Vec<String>> = {
+        let guard = shared.lock().await;
+        if guard.config.auto_batch {
+            autobatch_groups(&guard)
+        } else {
+            guard
+                .items
+                .iter()
+                .filter(|i| i.status != "succeeded")
+                .map(|i| vec![i.id.clone()])
+                .collect()
+        }
+    };
+
+    let mut handles = Vec::new();
+    for group in groups {
+        let permit = semaphore.clone().acquire_owned().await.map_err(|e| e.to_string())?;
+        if control.canceled.load(Ordering::SeqCst) {
+            drop(permit);
+            break;
+        }
+        let app_handle = app_handle.clone();
+        let shared = shared.clone();
+        let control = control.clone();
+        let creds = creds.clone();
+        handles.push(tokio::spawn(async move {
+            if group.len() == 1 {
+                run_item(app_handle, shared, control, creds, group.into_iter().next().unwrap()).await;
+            } else {
+                run_batch(app_handle, shared, control, creds, group).await;
+            }
+            drop(permit);
+        }));
+    }
+
+    for handle in handles {
+        let _ = handle.await;
+    }
+
+    // 收尾：根据取消/失败情况确定最终状态
+    {
+        let mut guard = shared.lock().await;
+        if control.canceled.load(Ordering::SeqCst) {
+            guard.status = "cancelled".to_string();
+        } else if guard.items.iter().any(|i| i.status == "failed") {
+            guard.status = "failed".to_string();
+        } else {
+            guard.status = "succeeded".to_string();
+        }
+        guard.completed_at = Some(now_iso());
+    }
+    persist_and_emit(&app_handle, &shared).await?;
+
+    Ok(())
+}
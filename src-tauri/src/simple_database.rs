@@ -4,6 +4,47 @@ use rusqlite::{Connection, params};
 // 简单的SQLite数据库实现，使用rusqlite
 pub struct SimpleDatabase;
 
+// 有序的迁移步骤。每一项都是一次性的、前向的 schema 变更，
+// 通过 SQLite 的 `PRAGMA user_version` 记录已应用到的位置，升级时幂等执行。
+// 只能向末尾追加，绝不修改或删除已发布的步骤。
+const MIGRATIONS: &[&str] = &[
+    // 0: 初始表结构
+    r#"
+    CREATE TABLE IF NOT EXISTS batch_tasks (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        type TEXT NOT NULL,
+        status TEXT NOT NULL,
+        progress INTEGER NOT NULL,
+        total_items INTEGER NOT NULL,
+        completed_items INTEGER NOT NULL,
+        failed_items INTEGER NOT NULL,
+        created_at TEXT NOT NULL,
+        started_at TEXT,
+        completed_at TEXT,
+        config_json TEXT NOT NULL,
+        items_json TEXT NOT NULL,
+        results_json TEXT NOT NULL,
+        error_text TEXT
+    )
+    "#,
+    // 1: 为状态/类型/时间过滤与分页建立索引
+    r#"
+    CREATE INDEX IF NOT EXISTS idx_batch_tasks_status ON batch_tasks (status);
+    CREATE INDEX IF NOT EXISTS idx_batch_tasks_type ON batch_tasks (type);
+    CREATE INDEX IF NOT EXISTS idx_batch_tasks_created_at ON batch_tasks (created_at);
+    "#,
+    // 2: 任务提示词/名称的全文检索虚拟表
+    r#"
+    CREATE VIRTUAL TABLE IF NOT EXISTS tasks_fts USING fts5(
+        task_id UNINDEXED,
+        item_id UNINDEXED,
+        name,
+        prompt
+    );
+    "#,
+];
+
 impl SimpleDatabase {
     // 获取数据库连接
     fn get_connection(app_handle: &AppHandle) -> Result<Connection, String> {
@@ -20,79 +61,174 @@ impl SimpleDatabase {
             .map_err(|e| format!("打开数据库失败: {}", e))
     }
 
-    // 初始化数据库
+    // 当前代码所对应的 schema 版本（即已定义的迁移步数），用于导入时校验
+    pub fn current_schema_version() -> i64 {
+        MIGRATIONS.len() as i64
+    }
+
+    // 初始化数据库：运行所有尚未应用的迁移
     pub async fn init_db(app_handle: &AppHandle) -> Result<(), String> {
-        let conn = Self::get_connection(app_handle)?;
-        
-        conn.execute(
-            r#"
-            CREATE TABLE IF NOT EXISTS batch_tasks (
-                id TEXT PRIMARY KEY,
-                name TEXT NOT NULL,
-                type TEXT NOT NULL,
-                status TEXT NOT NULL,
-                progress INTEGER NOT NULL,
-                total_items INTEGER NOT NULL,
-                completed_items INTEGER NOT NULL,
-                failed_items INTEGER NOT NULL,
-                created_at TEXT NOT NULL,
-                started_at TEXT,
-                completed_at TEXT,
-                config_json TEXT NOT NULL,
-                items_json TEXT NOT NULL,
-                results_json TEXT NOT NULL,
-                error_text TEXT
-            )
-            "#,
-            [],
-        ).map_err(|e| format!("创建表失败: {}", e))?;
-        
+        let mut conn = Self::get_connection(app_handle)?;
+        Self::run_migrations(&mut conn)
+    }
+
+    // 基于 `PRAGMA user_version` 的迁移执行器：
+    // 从当前版本开始，逐个在事务中执行 MIGRATIONS[i]（i >= user_version），并把版本推进到 i+1。
+    fn run_migrations(conn: &mut Connection) -> Result<(), String> {
+        let current: i64 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| format!("读取 user_version 失败: {}", e))?;
+
+        for (i, step) in MIGRATIONS.iter().enumerate() {
+            if (i as i64) < current {
+                continue;
+            }
+            let tx = conn
+                .transaction()
+                .map_err(|e| format!("开启迁移事务失败: {}", e))?;
+            tx.execute_batch(step)
+                .map_err(|e| format!("执行迁移 {} 失败: {}", i, e))?;
+            // user_version 不支持参数绑定，版本号为受控整数，直接拼接
+            tx.pragma_update(None, "user_version", (i as i64) + 1)
+                .map_err(|e| format!("更新 user_version 失败: {}", e))?;
+            tx.commit()
+                .map_err(|e| format!("提交迁移 {} 失败: {}", i, e))?;
+        }
+
         Ok(())
     }
 
+    // 从一行记录还原完整的 BatchTask
+    fn row_to_task(row: &rusqlite::Row) -> rusqlite::Result<crate::BatchTask> {
+        let config_json: String = row.get(11)?;
+        let items_json: String = row.get(12)?;
+        let results_json: String = row.get(13)?;
+
+        Ok(crate::BatchTask {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            r#type: row.get(2)?,
+            status: row.get(3)?,
+            progress: row.get(4)?,
+            total_items: row.get(5)?,
+            completed_items: row.get(6)?,
+            failed_items: row.get(7)?,
+            created_at: row.get(8)?,
+            started_at: row.get(9)?,
+            completed_at: row.get(10)?,
+            config: serde_json::from_str(&config_json)
+                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?,
+            items: serde_json::from_str(&items_json)
+                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?,
+            results: serde_json::from_str(&results_json)
+                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?,
+            error: row.get(14)?,
+        })
+    }
+
     // 获取所有批量任务
     pub async fn get_all_batch_tasks(app_handle: &AppHandle) -> Result<Vec<crate::BatchTask>, String> {
         let conn = Self::get_connection(app_handle)?;
-        
+
         let mut stmt = conn.prepare(
             "SELECT * FROM batch_tasks ORDER BY created_at DESC"
         ).map_err(|e| format!("准备查询失败: {}", e))?;
-        
-        let task_iter = stmt.query_map([], |row| {
-            let config_json: String = row.get(11)?;
-            let items_json: String = row.get(12)?;
-            let results_json: String = row.get(13)?;
-            
-            Ok(crate::BatchTask {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                r#type: row.get(2)?,
-                status: row.get(3)?,
-                progress: row.get(4)?,
-                total_items: row.get(5)?,
-                completed_items: row.get(6)?,
-                failed_items: row.get(7)?,
-                created_at: row.get(8)?,
-                started_at: row.get(9)?,
-                completed_at: row.get(10)?,
-                config: serde_json::from_str(&config_json)
-                    .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?,
-                items: serde_json::from_str(&items_json)
-                    .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?,
-                results: serde_json::from_str(&results_json)
-                    .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?,
-                error: row.get(14)?,
-            })
-        }).map_err(|e| format!("查询失败: {}", e))?;
-        
+
+        let task_iter = stmt.query_map([], Self::row_to_task)
+            .map_err(|e| format!("查询失败: {}", e))?;
+
         let mut tasks = Vec::new();
         for task in task_iter {
             tasks.push(task.map_err(|e| format!("读取任务失败: {}", e))?);
         }
-        
+
         Ok(tasks)
     }
 
+    // 带状态/类型过滤与分页的查询，只取摘要列，避免反序列化重型 JSON
+    pub async fn query_batch_tasks(
+        app_handle: &AppHandle,
+        status: Option<crate::TaskStatus>,
+        r#type: Option<crate::TaskType>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<Vec<crate::BatchTaskSummary>, String> {
+        let conn = Self::get_connection(app_handle)?;
+
+        let mut sql = String::from(
+            "SELECT id, name, type, status, progress, total_items, completed_items, \
+             failed_items, created_at, started_at, completed_at FROM batch_tasks",
+        );
+        // 过滤值已在命令层校验为枚举，此处用其规范字符串，安全可拼接
+        let mut conditions: Vec<String> = Vec::new();
+        if let Some(s) = status {
+            conditions.push(format!("status = '{}'", s.as_str()));
+        }
+        if let Some(t) = r#type {
+            conditions.push(format!("type = '{}'", t.as_str()));
+        }
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(" ORDER BY created_at DESC");
+        sql.push_str(&format!(
+            " LIMIT {} OFFSET {}",
+            limit.unwrap_or(-1),
+            offset.unwrap_or(0)
+        ));
+
+        let mut stmt = conn
+            .prepare(&sql)
+            .map_err(|e| format!("准备查询失败: {}", e))?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(crate::BatchTaskSummary {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    r#type: row.get(2)?,
+                    status: row.get(3)?,
+                    progress: row.get(4)?,
+                    total_items: row.get(5)?,
+                    completed_items: row.get(6)?,
+                    failed_items: row.get(7)?,
+                    created_at: row.get(8)?,
+                    started_at: row.get(9)?,
+                    completed_at: row.get(10)?,
+                })
+            })
+            .map_err(|e| format!("查询失败: {}", e))?;
+
+        let mut summaries = Vec::new();
+        for row in rows {
+            summaries.push(row.map_err(|e| format!("读取任务摘要失败: {}", e))?);
+        }
+
+        Ok(summaries)
+    }
+
+    // 获取单个任务的完整对象
+    pub async fn get_batch_task(
+        app_handle: &AppHandle,
+        task_id: &str,
+    ) -> Result<Option<crate::BatchTask>, String> {
+        let conn = Self::get_connection(app_handle)?;
+
+        let mut stmt = conn
+            .prepare("SELECT * FROM batch_tasks WHERE id = ?")
+            .map_err(|e| format!("准备查询失败: {}", e))?;
+
+        let mut rows = stmt
+            .query_map(params![task_id], Self::row_to_task)
+            .map_err(|e| format!("查询失败: {}", e))?;
+
+        match rows.next() {
+            Some(task) => Ok(Some(task.map_err(|e| format!("读取任务失败: {}", e))?)),
+            None => Ok(None),
+        }
+    }
+
     // 保存批量任务
     pub async fn save_batch_task(app_handle: &AppHandle, task: &crate::BatchTask) -> Result<(), String> {
         let conn = Self::get_connection(app_handle)?;
@@ -129,32 +265,84 @@ impl SimpleDatabase {
                 task.error,
             ],
         ).map_err(|e| format!("保存任务失败: {}", e))?;
-        
+
+        // 同步维护全文检索索引：先清掉本任务旧行，再按每个项目的提示词重建
+        conn.execute("DELETE FROM tasks_fts WHERE task_id = ?", params![task.id])
+            .map_err(|e| format!("清理检索索引失败: {}", e))?;
+        for item in &task.items {
+            conn.execute(
+                "INSERT INTO tasks_fts (task_id, item_id, name, prompt) VALUES (?, ?, ?, ?)",
+                params![task.id, item.id, task.name, item.prompt],
+            ).map_err(|e| format!("更新检索索引失败: {}", e))?;
+        }
+
         Ok(())
     }
 
     // 删除批量任务
     pub async fn delete_batch_task(app_handle: &AppHandle, task_id: &str) -> Result<(), String> {
         let conn = Self::get_connection(app_handle)?;
-        
+
         conn.execute(
             "DELETE FROM batch_tasks WHERE id = ?",
             params![task_id],
         ).map_err(|e| format!("删除任务失败: {}", e))?;
-        
+
+        conn.execute("DELETE FROM tasks_fts WHERE task_id = ?", params![task_id])
+            .map_err(|e| format!("清理检索索引失败: {}", e))?;
+
         Ok(())
     }
 
     // 清空所有批量任务
     pub async fn clear_batch_tasks(app_handle: &AppHandle) -> Result<(), String> {
         let conn = Self::get_connection(app_handle)?;
-        
+
         conn.execute("DELETE FROM batch_tasks", [])
             .map_err(|e| format!("清空任务失败: {}", e))?;
-        
+
+        conn.execute("DELETE FROM tasks_fts", [])
+            .map_err(|e| format!("清空检索索引失败: {}", e))?;
+
         Ok(())
     }
 
+    // 全文检索任务提示词与名称，返回按相关度排序的命中及匹配片段
+    pub async fn search_tasks(
+        app_handle: &AppHandle,
+        query: &str,
+        limit: Option<i64>,
+    ) -> Result<Vec<crate::SearchHit>, String> {
+        let conn = Self::get_connection(app_handle)?;
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT task_id, item_id, name, prompt, \
+                 snippet(tasks_fts, 3, '[', ']', '…', 12) \
+                 FROM tasks_fts WHERE tasks_fts MATCH ? ORDER BY rank LIMIT ?",
+            )
+            .map_err(|e| format!("准备检索失败: {}", e))?;
+
+        let rows = stmt
+            .query_map(params![query, limit.unwrap_or(50)], |row| {
+                Ok(crate::SearchHit {
+                    task_id: row.get(0)?,
+                    item_id: row.get(1)?,
+                    name: row.get(2)?,
+                    prompt: row.get(3)?,
+                    snippet: row.get(4)?,
+                })
+            })
+            .map_err(|e| format!("检索失败: {}", e))?;
+
+        let mut hits = Vec::new();
+        for row in rows {
+            hits.push(row.map_err(|e| format!("读取检索结果失败: {}", e))?);
+        }
+
+        Ok(hits)
+    }
+
     // 获取任务数量
     pub async fn get_task_count(app_handle: &AppHandle) -> Result<i64, String> {
         let conn = Self::get_connection(app_handle)?;
@@ -202,9 +390,13 @@ impl SimpleDatabase {
             }
             
             let final_sql = format!("DELETE FROM batch_tasks WHERE id IN ({})", params_str);
-            
+
             conn.execute(&final_sql, [])
                 .map_err(|e| format!("删除旧任务失败: {}", e))?;
+
+            let fts_sql = format!("DELETE FROM tasks_fts WHERE task_id IN ({})", params_str);
+            conn.execute(&fts_sql, [])
+                .map_err(|e| format!("清理检索索引失败: {}", e))?;
         }
         
         Ok(count)